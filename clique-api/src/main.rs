@@ -0,0 +1,32 @@
+//! An HTTP API over a Clique database, honouring data-erasure requests.
+//!
+//! The only mounted route is `DELETE /users/<user_id>`; no route serves aggregated points.
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, missing_docs)]
+mod response;
+mod routes;
+
+use clique_db::Database;
+
+#[derive(serde::Deserialize)]
+struct Config {
+    database_url: String,
+}
+
+/// Parse the config file, connect to the database, and launch the HTTP API.
+#[rocket::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+    let config: Config = toml::from_str(&std::fs::read_to_string("config.toml")?)?;
+    let db = Database::new(&config.database_url).await?;
+    rocket::build()
+        .manage(db)
+        .mount("/", rocket::routes![routes::delete_user])
+        .launch()
+        .await?;
+    Ok(())
+}
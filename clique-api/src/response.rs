@@ -1,11 +1,12 @@
 //! Various types to encode responses from the API.
 use rocket::{
-    http::Status,
+    http::{ContentType, Status},
     response::Responder,
     serde::{json::Json, msgpack::MsgPack},
-    Request,
+    Request, Response as RocketResponse,
 };
 use serde::Serialize;
+use std::io::Cursor;
 
 pub type Response<T> = Result<Object<T>, Error>;
 
@@ -15,22 +16,19 @@ struct ErrorBody {
     message: String,
 }
 
-impl From<clique_db::Error> for ErrorBody {
-    fn from(err: clique_db::Error) -> Self {
-        Self {
-            code: "database_connection",
-            message: err.to_string(),
-        }
-    }
-}
-
 pub struct Error {
     error: Object<ErrorBody>,
     status: Status,
 }
 
 impl Error {
-    pub const fn new(code: &'static str, message: String, status: Status) -> Self {
+    pub fn new(code: &'static str, message: String, status: Status) -> Self {
+        // Emit a diagnostic so server-side logs line up with the `code` returned to the client.
+        if status.code >= 500 {
+            tracing::error!(code, status = status.code, %message, "request failed");
+        } else {
+            tracing::warn!(code, status = status.code, %message, "request rejected");
+        }
         Self {
             error: Object(ErrorBody { code, message }),
             status,
@@ -40,9 +38,21 @@ impl Error {
 
 impl From<clique_db::Error> for Error {
     fn from(err: clique_db::Error) -> Self {
-        Self {
-            error: Object(err.into()),
-            status: Status::InternalServerError,
+        match err {
+            clique_db::Error::InvalidTimezone(_) => {
+                Self::new("invalid_timezone", err.to_string(), Status::BadRequest)
+            }
+            clique_db::Error::TimezoneUnsupported(_) => {
+                Self::new("timezone_unsupported", err.to_string(), Status::BadRequest)
+            }
+            clique_db::Error::PeriodUnsupported(_) => {
+                Self::new("period_unsupported", err.to_string(), Status::BadRequest)
+            }
+            _ => Self::new(
+                "database_connection",
+                err.to_string(),
+                Status::InternalServerError,
+            ),
         }
     }
 }
@@ -53,19 +63,68 @@ impl<'r, 'o: 'r> Responder<'r, 'o> for Error {
     }
 }
 
-pub struct Object<T: Serialize>(pub T);
+/// A response type that can be flattened into CSV for clients that ask for `text/csv`.
+///
+/// Every type wrapped in an [`Object`] must implement this trait, but only types that can be
+/// meaningfully tabulated override [`IntoCsv::into_csv`]; everything else takes the default `None`
+/// and falls back to the JSON/MsgPack negotiation.
+pub trait IntoCsv {
+    /// Serialize `self` into CSV, or return `None` if this type isn't tabular.
+    fn into_csv(&self) -> Option<csv::Result<Vec<u8>>> {
+        None
+    }
+}
+
+impl IntoCsv for ErrorBody {}
+
+impl IntoCsv for Vec<clique_db::PeriodData> {
+    fn into_csv(&self) -> Option<csv::Result<Vec<u8>>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let result = (|| {
+            writer.write_record(["period_start", "user1", "user2", "points"])?;
+            for period in self {
+                for pair in &period.pairs {
+                    writer.write_record([
+                        period.start.to_rfc3339(),
+                        pair.user1.to_string(),
+                        pair.user2.to_string(),
+                        pair.points.to_string(),
+                    ])?;
+                }
+            }
+            writer.into_inner().map_err(Into::into)
+        })();
+        Some(result)
+    }
+}
 
-impl<'r, 'o: 'r, T: Serialize> Responder<'r, 'o> for Object<T> {
+pub struct Object<T: Serialize + IntoCsv>(pub T);
+
+fn accepts(req: &Request<'_>, top: &str, sub: &str) -> bool {
+    req.accept()
+        .map(|accept| {
+            accept
+                .media_types()
+                .any(|mt| mt.top() == top && mt.sub() == sub)
+        })
+        .unwrap_or_default()
+}
+
+impl<'r, 'o: 'r, T: Serialize + IntoCsv> Responder<'r, 'o> for Object<T> {
     fn respond_to(self, req: &'r Request<'_>) -> rocket::response::Result<'o> {
-        let accepts_msgpack = req
-            .accept()
-            .map(|accept| {
-                accept
-                    .media_types()
-                    .any(|mt| mt.top() == "application" && mt.sub() == "msgpack")
-            })
-            .unwrap_or_default();
-        if accepts_msgpack {
+        if accepts(req, "text", "csv") {
+            if let Some(csv) = self.0.into_csv() {
+                let body = csv.map_err(|e| {
+                    tracing::error!(error = %e, "failed to serialize CSV response");
+                    Status::InternalServerError
+                })?;
+                return RocketResponse::build()
+                    .header(ContentType::new("text", "csv"))
+                    .sized_body(body.len(), Cursor::new(body))
+                    .ok();
+            }
+        }
+        if accepts(req, "application", "msgpack") {
             MsgPack(self.0).respond_to(req)
         } else {
             Json(self.0).respond_to(req)
@@ -0,0 +1,14 @@
+//! Route handlers for the Clique API.
+use crate::response::Error;
+use clique_db::{CliqueStore, Database};
+use rocket::{http::Status, State};
+
+/// Erase all stored data for a user, on their request.
+///
+/// Cascades to the user's messages and the points re-derived from them, so a successful call leaves
+/// no trace of the user behind.
+#[rocket::delete("/users/<user_id>")]
+pub async fn delete_user(db: &State<Database>, user_id: u64) -> Result<Status, Error> {
+    db.purge_user(user_id).await?;
+    Ok(Status::NoContent)
+}
@@ -0,0 +1,100 @@
+//! Historical backfill of channel message history.
+//!
+//! The `message` gateway event only records messages received live, so a freshly deployed instance
+//! has no history. After `ready`, [`backfill`] walks each accessible channel's history backwards in
+//! batches of 100, feeding every message through the same [`store_message`] path as the live
+//! handler until it reaches the configured cutoff. It is resumable: a channel that already has
+//! stored history is walked starting from its oldest known message, so repeated runs extend the
+//! archive further back rather than re-fetching.
+//!
+//! Backfill only ever walks *backwards* from the oldest stored message. Messages newer than the
+//! newest stored id that were missed while the bot was offline (the downtime gap between the last
+//! live message and `ready`) are not fetched, and the live handler only records messages sent
+//! after reconnecting, so such gaps stay unfilled.
+use crate::store_message;
+use clique_db::CliqueStore;
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId, MessageId},
+};
+
+/// The number of messages to request per page; Discord's maximum.
+const BATCH_SIZE: u64 = 100;
+
+/// Walk the history of every accessible channel in the given guilds, storing messages newer than
+/// `cutoff`.
+///
+/// Errors from a single channel are logged and skipped so one inaccessible channel doesn't abort
+/// the whole backfill.
+pub async fn backfill(
+    ctx: &Context,
+    db: &impl CliqueStore,
+    guilds: &[GuildId],
+    cutoff: clique_db::DateTime,
+) {
+    for &guild in guilds {
+        let channels = match guild.channels(&ctx.http).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                tracing::warn!(guild = guild.0, error = %e, "failed to list channels for backfill");
+                continue;
+            }
+        };
+        for channel in channels.into_keys() {
+            if let Err(e) = backfill_channel(ctx, db, guild, channel, cutoff).await {
+                tracing::warn!(channel = channel.0, error = %e, "backfill failed for channel");
+            }
+        }
+    }
+    tracing::info!("backfill complete");
+}
+
+#[tracing::instrument(skip(ctx, db), fields(guild = guild.0, channel = channel.0))]
+async fn backfill_channel(
+    ctx: &Context,
+    db: &impl CliqueStore,
+    guild: GuildId,
+    channel: ChannelId,
+    cutoff: clique_db::DateTime,
+) -> clique_db::DbResult<()> {
+    // Resume from the oldest message we've already stored, if any, so we only fetch older history.
+    let mut before = db.earliest_message(channel.0).await?.map(MessageId);
+    loop {
+        // serenity transparently respects Discord's rate limits, backing off as needed, so awaiting
+        // each page is all the rate-limiting we need.
+        let batch = channel
+            .messages(&ctx.http, |retriever| {
+                let retriever = retriever.limit(BATCH_SIZE);
+                match before {
+                    Some(id) => retriever.before(id),
+                    None => retriever,
+                }
+            })
+            .await;
+        let batch = match batch {
+            Ok(batch) => batch,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to fetch message batch");
+                return Ok(());
+            }
+        };
+        if batch.is_empty() {
+            return Ok(());
+        }
+        // `messages` returns newest-first; the oldest message is the last element and becomes the
+        // next page's before-cursor.
+        let oldest = batch.last().map(|m| m.id);
+        let reached_cutoff = batch.iter().any(|m| *m.timestamp < cutoff);
+        for msg in batch {
+            if *msg.timestamp < cutoff {
+                break;
+            }
+            // REST-fetched messages don't carry `guild_id`, so pass the known guild explicitly.
+            store_message(db, guild, msg).await?;
+        }
+        if reached_cutoff || oldest.is_none() {
+            return Ok(());
+        }
+        before = oldest;
+    }
+}
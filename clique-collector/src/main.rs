@@ -1,15 +1,34 @@
 //! A service which connects to Discord and stores message metadata in a database.
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, missing_docs)]
+mod backfill;
+
+use clique_db::CliqueStore;
 use serenity::{
     async_trait,
     client::{Client, Context, EventHandler},
     model::{
         channel::Message as SerenityMessage,
         gateway::{GatewayIntents, Ready},
+        guild::Member,
+        id::{ChannelId, GuildId, MessageId},
+        user::User,
     },
 };
+use std::sync::Arc;
 
-async fn store_message(db: &clique_db::Database, msg: SerenityMessage) -> clique_db::DbResult<()> {
+#[tracing::instrument(
+    skip(db, msg),
+    fields(
+        guild = guild.0,
+        channel = msg.channel_id.0,
+        message = msg.id.0,
+    ),
+)]
+async fn store_message(
+    db: &impl CliqueStore,
+    guild: GuildId,
+    msg: SerenityMessage,
+) -> clique_db::DbResult<()> {
     db.insert_user(msg.author.id.0, &msg.author.name).await?;
     let reply_to = match msg.referenced_message {
         Some(referenced) => {
@@ -21,7 +40,7 @@ async fn store_message(db: &clique_db::Database, msg: SerenityMessage) -> clique
     };
     clique_db::Message {
         id: msg.id.0,
-        guild: msg.guild_id.unwrap().0,
+        guild: guild.0,
         author: msg.author.id.0,
         channel: msg.channel_id.0,
         reply_to,
@@ -31,40 +50,133 @@ async fn store_message(db: &clique_db::Database, msg: SerenityMessage) -> clique
     .await
 }
 
-struct Collector(clique_db::Database);
+struct Collector {
+    db: Arc<clique_db::Database>,
+    /// How many days of history to backfill on startup, or `None` to disable backfill.
+    backfill_days: Option<u64>,
+}
 
 #[async_trait]
 impl EventHandler for Collector {
     /// Handle an incoming message and store it in the database.
+    ///
+    /// A failure to store a single message is logged and swallowed rather than propagated, so that
+    /// one bad insert can't take down the whole bot.
     async fn message(&self, _ctx: Context, msg: SerenityMessage) {
-        store_message(&self.0, msg)
-            .await
-            .expect("error while storing message");
+        // Direct messages carry no guild; we only track guild activity, so skip them.
+        let Some(guild) = msg.guild_id else {
+            return;
+        };
+        if let Err(e) = store_message(self.db.as_ref(), guild, msg).await {
+            tracing::warn!(error = %e, "failed to store message");
+        }
+    }
+
+    /// Remove a deleted message from the database.
+    async fn message_delete(
+        &self,
+        _ctx: Context,
+        _channel: ChannelId,
+        deleted: MessageId,
+        _guild: Option<GuildId>,
+    ) {
+        if let Err(e) = self.db.delete_message(deleted.0).await {
+            tracing::warn!(message = deleted.0, error = %e, "failed to delete message");
+        }
+    }
+
+    /// Remove a batch of deleted messages from the database.
+    async fn message_delete_bulk(
+        &self,
+        _ctx: Context,
+        _channel: ChannelId,
+        deleted: Vec<MessageId>,
+        _guild: Option<GuildId>,
+    ) {
+        let ids: Vec<u64> = deleted.iter().map(|id| id.0).collect();
+        if let Err(e) = self.db.delete_messages(&ids).await {
+            tracing::warn!(count = ids.len(), error = %e, "failed to delete messages");
+        }
     }
 
-    /// Log to the console once the service is running.
-    async fn ready(&self, _ctx: Context, _ready: Ready) {
-        eprintln!("Successfully connected to Discord and Postgres.");
+    /// Erase a departed member's data when they leave a guild.
+    async fn guild_member_removal(
+        &self,
+        _ctx: Context,
+        _guild: GuildId,
+        user: User,
+        _member: Option<Member>,
+    ) {
+        if let Err(e) = self.db.purge_user(user.id.0).await {
+            tracing::warn!(user = user.id.0, error = %e, "failed to purge user");
+        }
+    }
+
+    /// Log that the service is running and, if enabled, kick off the historical backfill.
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        tracing::info!("Successfully connected to Discord and the database.");
+        if let Some(days) = self.backfill_days {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
+            let guilds: Vec<_> = ready.guilds.iter().map(|guild| guild.id).collect();
+            backfill::backfill(&ctx, self.db.as_ref(), &guilds, cutoff).await;
+        }
     }
 }
 
 #[derive(serde::Deserialize)]
 struct Config {
-    postgres_url: String,
+    database_url: String,
     discord_token: String,
+    /// How many days of channel history to import on startup. Omit or set to 0 to disable.
+    #[serde(default)]
+    backfill_days: Option<u64>,
+    /// Maximum age in days to retain messages for. Omit or set to 0 to keep everything forever.
+    #[serde(default)]
+    retention_days: Option<u64>,
+}
+
+/// Periodically delete messages older than `days` days, once a day for as long as the bot runs.
+async fn retention_task(db: Arc<clique_db::Database>, days: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(24 * 60 * 60));
+    loop {
+        interval.tick().await;
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
+        match db.delete_messages_before(cutoff).await {
+            Ok(()) => tracing::info!(%cutoff, "applied retention window"),
+            Err(e) => tracing::warn!(error = %e, "retention sweep failed"),
+        }
+    }
 }
 
 /// Parse the config file and start the database and discord connections.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
     let config: Config = toml::from_str(&std::fs::read_to_string("config.toml")?)?;
-    let db = clique_db::Database::new(&config.postgres_url).await?;
-    let mut discord = Client::builder(config.discord_token, GatewayIntents::GUILD_MESSAGES)
-        .event_handler(Collector(db))
+    let db = Arc::new(clique_db::Database::new(&config.database_url).await?);
+    // Enforce the retention window in the background, deleting anything past its maximum age.
+    if let Some(days) = config.retention_days.filter(|&days| days > 0) {
+        tokio::spawn(retention_task(Arc::clone(&db), days));
+    }
+    // `GUILDS` lets us enumerate channels for backfill, `GUILD_MESSAGES` covers the live feed, and
+    // `GUILD_MEMBERS` (privileged) is needed for member-removal purges.
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MEMBERS;
+    let mut discord = Client::builder(config.discord_token, intents)
+        .event_handler(Collector {
+            db,
+            backfill_days: config.backfill_days.filter(|&days| days > 0),
+        })
         .await
         .expect("Error creating client");
     if let Err(e) = discord.start().await {
-        eprintln!("Discord client error: {e:?}");
+        tracing::error!(error = ?e, "Discord client error");
     }
     Ok(())
 }
@@ -0,0 +1,314 @@
+//! The [`PostgresStore`] backend, backed by [`tokio_postgres`].
+use crate::{CliqueStore, DbResult, Message};
+#[cfg(any(feature = "q_get_points", feature = "q_retention"))]
+use crate::DateTime;
+#[cfg(feature = "q_get_points")]
+use crate::{GetPoints, PeriodData, PeriodUserPoints};
+use async_trait::async_trait;
+use tokio_postgres::{connect, Client, NoTls, Statement};
+use tracing::Instrument;
+#[cfg(feature = "q_get_points")]
+use tokio_postgres::{
+    types::{FromSql, Type},
+    Row,
+};
+#[cfg(feature = "q_get_points")]
+use {fallible_iterator::FallibleIterator, std::error::Error as StdError};
+
+/// A [`CliqueStore`] implementation backed by a Postgres server.
+///
+/// This struct holds the connection client along with the prepared statements for each enabled
+/// query, and should ideally be created once and long-lived.
+pub struct PostgresStore {
+    client: Client,
+    #[cfg(feature = "q_get_points")]
+    get_points: Statement,
+    #[cfg(feature = "q_get_user")]
+    get_user: Statement,
+    #[cfg(feature = "q_insert_message")]
+    insert_message: Statement,
+    #[cfg(feature = "q_insert_user")]
+    insert_user: Statement,
+    #[cfg(feature = "q_earliest_message")]
+    earliest_message: Statement,
+    #[cfg(feature = "q_delete_message")]
+    delete_message: Statement,
+    #[cfg(feature = "q_delete_message")]
+    delete_messages: Statement,
+    // `purge_user` runs its deletes in a real transaction, which needs an owned `&mut Client`
+    // (the shared `client` above pipelines concurrent callers and offers no per-task scope), so it
+    // opens a dedicated connection from this URL rather than using prepared statements.
+    #[cfg(feature = "q_purge_user")]
+    db_url: String,
+    #[cfg(feature = "q_retention")]
+    delete_messages_before: Statement,
+}
+
+async fn init_db(db_url: &str) -> DbResult<Client> {
+    let (client, connection) = connect(db_url, NoTls).await?;
+    tokio::spawn(
+        async move {
+            if let Err(e) = connection.await {
+                tracing::error!(error = %e, "connection error");
+            }
+        }
+        .instrument(tracing::info_span!("postgres_connection")),
+    );
+    client
+        .execute(include_str!("queries/user_table.sql"), &[])
+        .await?;
+    client
+        .execute(include_str!("queries/message_table.sql"), &[])
+        .await?;
+    Ok(client)
+}
+
+/// Open a fresh connection to the database without touching the schema.
+///
+/// Used for operations that need their own isolated transaction scope rather than sharing the
+/// pipelined [`PostgresStore::client`].
+#[cfg(feature = "q_purge_user")]
+async fn dedicated_connection(db_url: &str) -> DbResult<Client> {
+    let (client, connection) = connect(db_url, NoTls).await?;
+    tokio::spawn(
+        async move {
+            if let Err(e) = connection.await {
+                tracing::error!(error = %e, "connection error");
+            }
+        }
+        .instrument(tracing::info_span!("postgres_connection")),
+    );
+    Ok(client)
+}
+
+impl PostgresStore {
+    /// Connect to the Postgres database and prepare statements, creating tables if they don't exist.
+    ///
+    /// # Errors
+    ///
+    /// If the connection URL is invalid, the database cannot be connected to, or the tables cannot
+    /// be created.
+    pub async fn new(db_url: &str) -> DbResult<Self> {
+        let client = init_db(db_url).await?;
+        Ok(Self {
+            #[cfg(feature = "q_get_points")]
+            get_points: client
+                .prepare(include_str!("queries/get_points.sql"))
+                .await?,
+            #[cfg(feature = "q_get_user")]
+            get_user: client.prepare(include_str!("queries/get_user.sql")).await?,
+            #[cfg(feature = "q_insert_message")]
+            insert_message: client
+                .prepare(include_str!("queries/insert_message.sql"))
+                .await?,
+            #[cfg(feature = "q_insert_user")]
+            insert_user: client
+                .prepare(include_str!("queries/insert_user.sql"))
+                .await?,
+            #[cfg(feature = "q_earliest_message")]
+            earliest_message: client
+                .prepare(include_str!("queries/earliest_message.sql"))
+                .await?,
+            #[cfg(feature = "q_delete_message")]
+            delete_message: client
+                .prepare(include_str!("queries/delete_message.sql"))
+                .await?,
+            #[cfg(feature = "q_delete_message")]
+            delete_messages: client
+                .prepare(include_str!("queries/delete_messages.sql"))
+                .await?,
+            #[cfg(feature = "q_purge_user")]
+            db_url: db_url.to_owned(),
+            #[cfg(feature = "q_retention")]
+            delete_messages_before: client
+                .prepare(include_str!("queries/delete_messages_before.sql"))
+                .await?,
+            // This field goes last because it moves `client`, which is used in the other field
+            // initializers.
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl CliqueStore for PostgresStore {
+    #[cfg(feature = "q_get_user")]
+    async fn get_user(&self, user_id: u64) -> DbResult<Option<String>> {
+        let row = self
+            .client
+            .query_opt(&self.get_user, &[&(user_id as i64)])
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    #[cfg(feature = "q_insert_user")]
+    async fn insert_user(&self, user_id: u64, name: &str) -> DbResult<()> {
+        self.client
+            .execute(&self.insert_user, &[&(user_id as i64), &name])
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_insert_message")]
+    async fn insert_message(&self, message: &Message) -> DbResult<()> {
+        self.client
+            .execute(
+                &self.insert_message,
+                &[
+                    &(message.id as i64),
+                    &(message.guild as i64),
+                    &(message.author as i64),
+                    &(message.channel as i64),
+                    &message.reply_to.map(|id| id as i64),
+                    &message.timestamp.naive_utc(),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_earliest_message")]
+    async fn earliest_message(&self, channel: u64) -> DbResult<Option<u64>> {
+        let row = self
+            .client
+            .query_opt(&self.earliest_message, &[&(channel as i64)])
+            .await?;
+        Ok(row.and_then(|row| row.get::<_, Option<i64>>(0).map(|id| id as u64)))
+    }
+
+    #[cfg(feature = "q_delete_message")]
+    async fn delete_message(&self, id: u64) -> DbResult<()> {
+        self.client
+            .execute(&self.delete_message, &[&(id as i64)])
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_delete_message")]
+    async fn delete_messages(&self, ids: &[u64]) -> DbResult<()> {
+        let ids: Vec<i64> = ids.iter().map(|&id| id as i64).collect();
+        self.client
+            .execute(&self.delete_messages, &[&ids])
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_purge_user")]
+    async fn purge_user(&self, user_id: u64) -> DbResult<()> {
+        let user_id = user_id as i64;
+        // Drop the user's own messages, orphan any replies that pointed at them so no surviving
+        // message forms a half-pair, then remove the user row itself. All three must apply together
+        // or not at all. The shared `client` pipelines concurrent callers onto one connection with
+        // no per-task transaction scope, so a `BEGIN` there would entangle unrelated queries and
+        // race with a second purge; open a dedicated connection and use a real `transaction()`,
+        // which rolls back automatically if it's dropped without committing.
+        let mut client = dedicated_connection(&self.db_url).await?;
+        let tx = client.transaction().await?;
+        tx.execute(include_str!("queries/purge_user_messages.sql"), &[&user_id])
+            .await?;
+        tx.execute(include_str!("queries/purge_user_replies.sql"), &[&user_id])
+            .await?;
+        tx.execute(include_str!("queries/purge_user_row.sql"), &[&user_id])
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_retention")]
+    async fn delete_messages_before(&self, before: DateTime) -> DbResult<()> {
+        self.client
+            .execute(&self.delete_messages_before, &[&before.naive_utc()])
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_get_points")]
+    async fn get_points(&self, query: &GetPoints) -> DbResult<Vec<PeriodData>> {
+        self.client
+            .query(
+                &self.get_points,
+                &[
+                    &query.period.to_string(),
+                    &query.guild.map(|g| g as i64),
+                    &query.after.map(|t| t.naive_utc()),
+                    &query.before.map(|t| t.naive_utc()),
+                    &query.timezone,
+                ],
+            )
+            .await
+            .map_err(Into::into)
+            .map(|rows| rows.into_iter().map(PeriodData::from).collect())
+    }
+}
+
+// The raw `RECORD_ARRAY` decoding below is specific to the way Postgres returns the aggregated
+// pairs from `get_points.sql`; it lives here rather than in the generic `get_points` module so that
+// backends which return points as plain columns (e.g. SQLite) don't drag in the binary format.
+#[cfg(feature = "q_get_points")]
+impl From<Row> for PeriodData {
+    fn from(row: Row) -> Self {
+        let naive_start: chrono::NaiveDateTime = row.get(0);
+        let points: PeriodUserPointsVec = row.get(1);
+        Self {
+            start: DateTime::from_utc(naive_start, chrono::Utc),
+            pairs: points.0,
+        }
+    }
+}
+
+/// A new type wrapper around a [`Vec<PeriodUserPoints>`] which implements [`FromSql`].
+/// Not sure why, but implementing [`FromSql`] on [`PeriodUserPoints`] directly didn't work.
+#[cfg(feature = "q_get_points")]
+struct PeriodUserPointsVec(Vec<PeriodUserPoints>);
+
+#[cfg(feature = "q_get_points")]
+impl<'a> FromSql<'a> for PeriodUserPointsVec {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        let array = postgres_protocol::types::array_from_sql(raw)?;
+        array
+            .values()
+            .iterator()
+            .map(|value| match value {
+                Ok(Some(value)) => Ok(period_user_points_from_record(value)),
+                Ok(None) => Err("unexpected null value".into()),
+                Err(e) => Err(e),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty, &Type::RECORD_ARRAY)
+    }
+}
+
+#[cfg(feature = "q_get_points")]
+fn array_slice<const LEN: usize, T: Copy>(slice: &[T], offset: usize) -> [T; LEN] {
+    std::array::from_fn(|i| slice[offset + i])
+}
+
+#[cfg(feature = "q_get_points")]
+fn read_u64(slice: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(array_slice(slice, offset))
+}
+
+#[cfg(feature = "q_get_points")]
+fn period_user_points_from_record(value: &[u8]) -> PeriodUserPoints {
+    // I couldn't find documentation on the binary format used here, so the comments below are
+    // just guesses based on observation.
+    // 0..4: the number of fields (3)
+    // 4..8: the type of the first field (20)
+    // 8..12: the length of the first field (8)
+    let user1 = read_u64(value, 12);
+    // 20..24: the type of the second field (20)
+    // 24..28: the length of the second field (8)
+    let user2 = read_u64(value, 28);
+    // 36..40: the type of the third field (20)
+    // 40..44: the length of the third field (8)
+    let points = read_u64(value, 44);
+    PeriodUserPoints {
+        user1,
+        user2,
+        points,
+    }
+}
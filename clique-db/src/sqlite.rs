@@ -0,0 +1,252 @@
+//! The [`SqliteStore`] backend, backed by [`tokio_rusqlite`].
+//!
+//! This is aimed at small self-hosters who don't want to run a separate Postgres server. Unlike
+//! [`PostgresStore`](crate::postgres::PostgresStore), SQLite has no server-side array aggregation,
+//! so [`CliqueStore::get_points`] returns one row per user pair and the pairs are grouped into
+//! [`PeriodData`] buckets in Rust.
+use crate::{CliqueStore, DbResult, Message};
+#[cfg(any(feature = "q_get_points", feature = "q_retention"))]
+use crate::DateTime;
+#[cfg(feature = "q_get_points")]
+use crate::{GetPoints, PeriodData, PeriodUserPoints, TimePeriod};
+use async_trait::async_trait;
+#[cfg(any(feature = "q_get_user", feature = "q_earliest_message"))]
+use rusqlite::OptionalExtension;
+use tokio_rusqlite::Connection;
+
+/// A [`CliqueStore`] implementation backed by a local SQLite database file.
+///
+/// This struct should ideally be created once and long-lived.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Open the SQLite database, creating tables if they don't exist.
+    ///
+    /// `path` is the filesystem path to the database file (the `sqlite://` prefix should already be
+    /// stripped by [`Database::new`](crate::Database::new)).
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be opened or the tables cannot be created.
+    pub async fn new(path: &str) -> DbResult<Self> {
+        let connection = Connection::open(path).await?;
+        connection
+            .call(|conn| {
+                conn.execute_batch(include_str!("queries/sqlite/tables.sql"))?;
+                Ok(())
+            })
+            .await?;
+        Ok(Self { connection })
+    }
+}
+
+#[async_trait]
+impl CliqueStore for SqliteStore {
+    #[cfg(feature = "q_get_user")]
+    async fn get_user(&self, user_id: u64) -> DbResult<Option<String>> {
+        let name = self
+            .connection
+            .call(move |conn| {
+                conn.query_row(
+                    include_str!("queries/sqlite/get_user.sql"),
+                    [user_id as i64],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+            })
+            .await?;
+        Ok(name)
+    }
+
+    #[cfg(feature = "q_insert_user")]
+    async fn insert_user(&self, user_id: u64, name: &str) -> DbResult<()> {
+        let name = name.to_owned();
+        self.connection
+            .call(move |conn| {
+                conn.execute(
+                    include_str!("queries/sqlite/insert_user.sql"),
+                    rusqlite::params![user_id as i64, name],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_insert_message")]
+    async fn insert_message(&self, message: &Message) -> DbResult<()> {
+        let params = (
+            message.id as i64,
+            message.guild as i64,
+            message.author as i64,
+            message.channel as i64,
+            message.reply_to.map(|id| id as i64),
+            message.timestamp.naive_utc(),
+        );
+        self.connection
+            .call(move |conn| {
+                conn.execute(
+                    include_str!("queries/sqlite/insert_message.sql"),
+                    rusqlite::params![params.0, params.1, params.2, params.3, params.4, params.5],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_earliest_message")]
+    async fn earliest_message(&self, channel: u64) -> DbResult<Option<u64>> {
+        let id = self
+            .connection
+            .call(move |conn| {
+                conn.query_row(
+                    include_str!("queries/sqlite/earliest_message.sql"),
+                    [channel as i64],
+                    |row| row.get::<_, Option<i64>>(0),
+                )
+                .optional()
+            })
+            .await?;
+        Ok(id.flatten().map(|id| id as u64))
+    }
+
+    #[cfg(feature = "q_delete_message")]
+    async fn delete_message(&self, id: u64) -> DbResult<()> {
+        self.connection
+            .call(move |conn| {
+                conn.execute(
+                    include_str!("queries/sqlite/delete_message.sql"),
+                    [id as i64],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_delete_message")]
+    async fn delete_messages(&self, ids: &[u64]) -> DbResult<()> {
+        let ids: Vec<i64> = ids.iter().map(|&id| id as i64).collect();
+        self.connection
+            .call(move |conn| {
+                let mut stmt = conn.prepare(include_str!("queries/sqlite/delete_message.sql"))?;
+                for id in ids {
+                    stmt.execute([id])?;
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_purge_user")]
+    async fn purge_user(&self, user_id: u64) -> DbResult<()> {
+        let user_id = user_id as i64;
+        self.connection
+            .call(move |conn| {
+                // Run the three steps in a transaction so a purge either fully completes or not at
+                // all, keeping the pair-ordering invariant intact.
+                let tx = conn.transaction()?;
+                tx.execute(include_str!("queries/sqlite/purge_user_messages.sql"), [user_id])?;
+                tx.execute(include_str!("queries/sqlite/purge_user_replies.sql"), [user_id])?;
+                tx.execute(include_str!("queries/sqlite/purge_user_row.sql"), [user_id])?;
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_retention")]
+    async fn delete_messages_before(&self, before: DateTime) -> DbResult<()> {
+        let before = before.naive_utc();
+        self.connection
+            .call(move |conn| {
+                conn.execute(
+                    include_str!("queries/sqlite/delete_messages_before.sql"),
+                    [before],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "q_get_points")]
+    async fn get_points(&self, query: &GetPoints) -> DbResult<Vec<PeriodData>> {
+        // SQLite has no equivalent of Postgres's three-argument `date_trunc`, so buckets always
+        // start at UTC midnight. Rather than silently returning UTC-aligned buckets for a request
+        // that asked for a specific zone, reject the query so the caller sees the zone was dropped.
+        if let Some(tz) = &query.timezone {
+            return Err(crate::Error::TimezoneUnsupported(tz.clone()));
+        }
+        // `strftime` can only express the calendar buckets below; sub-second, quarter and
+        // multi-year periods have no `strftime` form, so reject them rather than fall through to a
+        // full-precision bucket that never aggregates (Postgres handles them via `date_trunc`).
+        match query.period {
+            TimePeriod::Second
+            | TimePeriod::Minute
+            | TimePeriod::Hour
+            | TimePeriod::Day
+            | TimePeriod::Week
+            | TimePeriod::Month
+            | TimePeriod::Year => {}
+            TimePeriod::Microsecond
+            | TimePeriod::Millisecond
+            | TimePeriod::Quarter
+            | TimePeriod::Decade
+            | TimePeriod::Century
+            | TimePeriod::Millennium => {
+                return Err(crate::Error::PeriodUnsupported(query.period.to_string()));
+            }
+        }
+        let period = query.period.to_string();
+        let guild = query.guild.map(|g| g as i64);
+        let after = query.after.map(|t| t.naive_utc());
+        let before = query.before.map(|t| t.naive_utc());
+        let rows = self
+            .connection
+            .call(move |conn| {
+                let mut stmt = conn.prepare(include_str!("queries/sqlite/get_points.sql"))?;
+                let rows = stmt
+                    .query_map(
+                        rusqlite::params![period, guild, after, before],
+                        |row| {
+                            Ok((
+                                row.get::<_, chrono::NaiveDateTime>(0)?,
+                                row.get::<_, i64>(1)? as u64,
+                                row.get::<_, i64>(2)? as u64,
+                                row.get::<_, i64>(3)? as u64,
+                            ))
+                        },
+                    )?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+
+        // SQLite returns one flat row per `(period_start, user1, user2, points)`; collapse
+        // consecutive rows sharing a period start into a single `PeriodData` bucket. The query
+        // orders by period start so equal starts are always adjacent.
+        let mut data: Vec<PeriodData> = Vec::new();
+        for (naive_start, user1, user2, points) in rows {
+            let start = DateTime::from_utc(naive_start, chrono::Utc);
+            let pair = PeriodUserPoints {
+                user1,
+                user2,
+                points,
+            };
+            match data.last_mut() {
+                Some(period) if period.start == start => period.pairs.push(pair),
+                _ => data.push(PeriodData {
+                    start,
+                    pairs: vec![pair],
+                }),
+            }
+        }
+        Ok(data)
+    }
+}